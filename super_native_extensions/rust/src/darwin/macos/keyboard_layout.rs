@@ -6,21 +6,25 @@ use std::{
 };
 
 use core_foundation::{
-    base::CFRelease,
+    array::CFArray,
+    base::{CFRelease, CFType, TCFType},
     data::{CFDataGetBytePtr, CFDataRef},
-    dictionary::CFDictionaryRef,
-    string::CFStringRef,
+    dictionary::{CFDictionary, CFDictionaryRef},
+    string::{CFString, CFStringRef},
 };
 use nativeshell_core::util::Late;
 
 use crate::keyboard_layout_manager::{Key, KeyboardLayout, KeyboardLayoutDelegate};
 
 use super::keyboard_layout_sys::{
-    altKey, cmdKey, kTISNotifySelectedKeyboardInputSourceChanged, kTISPropertyUnicodeKeyLayoutData,
-    kUCKeyActionDisplay, kUCKeyTranslateNoDeadKeysMask, shiftKey, CFNotificationCenterAddObserver,
-    CFNotificationCenterGetDistributedCenter, CFNotificationCenterRef,
-    CFNotificationCenterRemoveObserver, CFNotificationSuspensionBehaviorCoalesce, CFObject,
-    LMGetKbdType, TISCopyCurrentASCIICapableKeyboardLayoutInputSource, TISGetInputSourceProperty,
+    altKey, cmdKey, controlKey, kTISNotifySelectedKeyboardInputSourceChanged,
+    kTISPropertyInputSourceID, kTISPropertyInputSourceType, kTISPropertyLocalizedName,
+    kTISPropertyUnicodeKeyLayoutData, kTISTypeKeyboardLayout, kUCKeyActionDown, kVK_Space,
+    shiftKey, CFNotificationCenterAddObserver, CFNotificationCenterGetDistributedCenter,
+    CFNotificationCenterRef, CFNotificationCenterRemoveObserver,
+    CFNotificationSuspensionBehaviorCoalesce, CFObject, LMGetKbdType,
+    TISCopyCurrentASCIICapableKeyboardLayoutInputSource, TISCopyCurrentKeyboardInputSource,
+    TISCopyCurrentKeyboardLayoutInputSource, TISCreateInputSourceList, TISGetInputSourceProperty,
     UCKeyTranslate,
 };
 
@@ -56,18 +60,184 @@ impl PlatformKeyboardLayout {
         let key_map = get_key_map();
         unsafe {
             let input_source = TISCopyCurrentASCIICapableKeyboardLayoutInputSource();
-            let layout_data: CFObject =
-                TISGetInputSourceProperty(input_source, kTISPropertyUnicodeKeyLayoutData);
+            let mut layout = self
+                .keyboard_layout_from_input_source(input_source, &key_map)
+                .expect("ASCII-capable input source has unicode key layout data");
+            CFRelease(input_source);
+
+            let true_source = Self::current_layout_input_source();
+            let true_layout_data: CFObject =
+                TISGetInputSourceProperty(true_source, kTISPropertyUnicodeKeyLayoutData);
+            if !true_layout_data.is_null() {
+                for (key, entry) in layout.keys.iter_mut().zip(key_map.iter()) {
+                    let (logical_true, is_dead_true) =
+                        Self::true_logical_for_entry(entry, true_layout_data);
+                    key.logical_true = logical_true;
+                    key.is_dead_true = is_dead_true;
+                }
+            }
+            CFRelease(true_source);
+
+            layout
+        }
+    }
+
+    /// The input source backing `logical_true`: the layout the user actually
+    /// has selected, not the ASCII-capable substitute macOS reports for
+    /// Cyrillic/Greek/Arabic layouts. Falls back to the ASCII-capable source
+    /// (always present) if neither candidate has unicode key layout data.
+    unsafe fn current_layout_input_source() -> CFObject {
+        let source = TISCopyCurrentKeyboardInputSource();
+        if !source.is_null() {
+            if !TISGetInputSourceProperty(source, kTISPropertyUnicodeKeyLayoutData).is_null() {
+                return source;
+            }
+            CFRelease(source);
+        }
+
+        let source = TISCopyCurrentKeyboardLayoutInputSource();
+        if !source.is_null() {
+            if !TISGetInputSourceProperty(source, kTISPropertyUnicodeKeyLayoutData).is_null() {
+                return source;
+            }
+            CFRelease(source);
+        }
 
-            let keys: Vec<Key> = key_map
+        TISCopyCurrentASCIICapableKeyboardLayoutInputSource()
+    }
+
+    unsafe fn true_logical_for_entry(
+        entry: &KeyMapEntry,
+        layout_data: CFObject,
+    ) -> (Option<i64>, bool) {
+        if let Some(logical) = entry.logical {
+            return (Some(logical), false);
+        }
+        let layout = CFDataGetBytePtr(layout_data as CFDataRef);
+        Self::translate_logical(layout, entry.platform, 0)
+    }
+
+    /// Every installed keyboard layout, not just the one currently active.
+    /// Lets callers precompute shortcut mappings for layouts the user might
+    /// switch to instead of only reacting after `keyboard_map_did_change`.
+    pub fn get_available_layouts(&self) -> Vec<KeyboardLayout> {
+        let key_map = get_key_map();
+        unsafe {
+            let filter = CFDictionary::from_CFType_pairs(&[(
+                CFString::wrap_under_get_rule(kTISPropertyInputSourceType),
+                CFString::wrap_under_get_rule(kTISTypeKeyboardLayout).as_CFType(),
+            )]);
+
+            // includeAllInstalled = true: enumerate every installed layout, not
+            // just the ones currently enabled in System Settings.
+            let sources = TISCreateInputSourceList(filter.as_concrete_TypeRef(), 1);
+            let sources: CFArray<CFType> = CFArray::wrap_under_create_rule(sources);
+
+            sources
                 .iter()
-                .map(|a| self.key_from_entry(a, layout_data))
-                .collect();
+                .filter_map(|source| {
+                    let source = source.as_CFTypeRef() as CFObject;
+                    self.keyboard_layout_from_input_source(source, &key_map)
+                })
+                .collect()
+        }
+    }
 
-            CFRelease(input_source);
+    /// Builds a `KeyboardLayout` from `input_source`, or `None` if it has no
+    /// unicode key layout data to translate (e.g. a non-keyboard-layout input
+    /// source slipping through a filter).
+    unsafe fn keyboard_layout_from_input_source(
+        &self,
+        input_source: CFObject,
+        key_map: &[KeyMapEntry],
+    ) -> Option<KeyboardLayout> {
+        let layout_data: CFObject =
+            TISGetInputSourceProperty(input_source, kTISPropertyUnicodeKeyLayoutData);
+        if layout_data.is_null() {
+            return None;
+        }
 
-            KeyboardLayout { keys }
+        let id = Self::cfstring_property(input_source, kTISPropertyInputSourceID);
+        let localized_name = Self::cfstring_property(input_source, kTISPropertyLocalizedName);
+
+        let keys: Vec<Key> = key_map
+            .iter()
+            .map(|a| self.key_from_entry(a, layout_data))
+            .collect();
+
+        Some(KeyboardLayout {
+            id,
+            localized_name,
+            keys,
+        })
+    }
+
+    unsafe fn cfstring_property(input_source: CFObject, property_key: CFStringRef) -> String {
+        let value = TISGetInputSourceProperty(input_source, property_key) as CFStringRef;
+        if value.is_null() {
+            return String::new();
         }
+        CFString::wrap_under_get_rule(value).to_string()
+    }
+
+    /// Translates `platform` under `modifier_key_state`, resolving dead keys to
+    /// the spacing glyph they display rather than letting them silently
+    /// combine with (or swallow) the next keystroke.
+    ///
+    /// Each call starts from its own zeroed `dead_key_state`, so state from
+    /// one key or modifier combination never leaks into the next.
+    unsafe fn translate_logical(
+        layout: *const u8,
+        platform: i64,
+        modifier_key_state: u32,
+    ) -> (Option<i64>, bool) {
+        let mut dead_key_state: u32 = 0;
+        let mut unichar: u16 = 0;
+        let mut unichar_count: c_ulong = 0;
+
+        UCKeyTranslate(
+            layout as *mut _,
+            platform as u16,
+            kUCKeyActionDown,
+            modifier_key_state,
+            LMGetKbdType(),
+            0,
+            &mut dead_key_state as *mut _,
+            1,
+            &mut unichar_count as *mut _,
+            &mut unichar as *mut _,
+        );
+
+        if unichar_count > 0 {
+            return (Some(unichar as i64), false);
+        }
+
+        if dead_key_state == 0 {
+            return (None, false);
+        }
+
+        // Dead key: feed the pending dead_key_state back in against the space
+        // key, which emits the spacing form of the diacritic (e.g. `^` for a
+        // dead circumflex) instead of combining it with another character.
+        UCKeyTranslate(
+            layout as *mut _,
+            kVK_Space,
+            kUCKeyActionDown,
+            0,
+            LMGetKbdType(),
+            0,
+            &mut dead_key_state as *mut _,
+            1,
+            &mut unichar_count as *mut _,
+            &mut unichar as *mut _,
+        );
+
+        let logical = if unichar_count > 0 {
+            Some(unichar as i64)
+        } else {
+            None
+        };
+        (logical, true)
     }
 
     unsafe fn key_from_entry(&self, entry: &KeyMapEntry, layout_data: CFObject) -> Key {
@@ -80,88 +250,33 @@ impl PlatformKeyboardLayout {
                 logical_alt: None,
                 logical_alt_shift: None,
                 logical_meta: None,
+                logical_control: None,
+                logical_true: Some(logical),
+                is_dead: false,
+                is_dead_shift: false,
+                is_dead_alt: false,
+                is_dead_alt_shift: false,
+                is_dead_meta: false,
+                is_dead_control: false,
+                is_dead_true: false,
             },
             None => {
-                let mut logical_key = None::<i64>;
-                let mut logical_key_shift = None::<i64>;
-                let mut logical_key_alt = None::<i64>;
-                let mut logical_key_alt_shift = None::<i64>;
-                let mut logical_key_cmd = None::<i64>;
-
-                let mut dead_key_state: u32 = 0;
-                let mut unichar: u16 = 0;
-                let mut unichar_count: c_ulong = 0;
-
                 let layout = CFDataGetBytePtr(layout_data as CFDataRef);
 
-                UCKeyTranslate(
-                    layout as *mut _,
-                    entry.platform as u16,
-                    kUCKeyActionDisplay,
-                    0,
-                    LMGetKbdType(),
-                    kUCKeyTranslateNoDeadKeysMask,
-                    &mut dead_key_state as *mut _,
-                    1,
-                    &mut unichar_count as *mut _,
-                    &mut unichar as *mut _,
-                );
+                let (logical_key, is_dead) = Self::translate_logical(layout, entry.platform, 0);
 
-                if unichar_count > 0 {
-                    logical_key.replace(unichar as i64);
-                }
-
-                UCKeyTranslate(
-                    layout as *mut _,
-                    entry.platform as u16,
-                    kUCKeyActionDisplay,
-                    (shiftKey >> 8) & 0xFF,
-                    LMGetKbdType(),
-                    kUCKeyTranslateNoDeadKeysMask,
-                    &mut dead_key_state as *mut _,
-                    1,
-                    &mut unichar_count as *mut _,
-                    &mut unichar as *mut _,
-                );
-
-                if unichar_count > 0 {
-                    logical_key_shift.replace(unichar as i64);
-                }
-
-                UCKeyTranslate(
-                    layout as *mut _,
-                    entry.platform as u16,
-                    kUCKeyActionDisplay,
-                    (altKey >> 8) & 0xFF,
-                    LMGetKbdType(),
-                    kUCKeyTranslateNoDeadKeysMask,
-                    &mut dead_key_state as *mut _,
-                    1,
-                    &mut unichar_count as *mut _,
-                    &mut unichar as *mut _,
-                );
+                let (logical_key_shift, is_dead_shift) =
+                    Self::translate_logical(layout, entry.platform, (shiftKey >> 8) & 0xFF);
 
-                if unichar_count > 0 {
-                    logical_key_alt.replace(unichar as i64);
-                }
+                let (logical_key_alt, is_dead_alt) =
+                    Self::translate_logical(layout, entry.platform, (altKey >> 8) & 0xFF);
 
-                UCKeyTranslate(
-                    layout as *mut _,
-                    entry.platform as u16,
-                    kUCKeyActionDisplay,
+                let (logical_key_alt_shift, is_dead_alt_shift) = Self::translate_logical(
+                    layout,
+                    entry.platform,
                     (shiftKey >> 8) & 0xFF | (altKey >> 8) & 0xFF,
-                    LMGetKbdType(),
-                    kUCKeyTranslateNoDeadKeysMask,
-                    &mut dead_key_state as *mut _,
-                    1,
-                    &mut unichar_count as *mut _,
-                    &mut unichar as *mut _,
                 );
 
-                if unichar_count > 0 {
-                    logical_key_alt_shift.replace(unichar as i64);
-                }
-
                 // On some keyboard (SVK), using CMD modifier keys when specifying keyboard
                 // shortcut results in results in US layout key matched. So we need to know
                 // the value with CMD modifier as well.
@@ -170,22 +285,14 @@ impl PlatformKeyboardLayout {
                 // On the other hand ' key on French AZERTY is ù, and CMD + ù key equivalent
                 // is matched. That's possibly because UCKeyTranslate CMD + ] on SVK keyboard returns ],
                 // whereas on French AZERTY UCKeyTranslate CMD + ' returns ù.
-                UCKeyTranslate(
-                    layout as *mut _,
-                    entry.platform as u16,
-                    kUCKeyActionDisplay,
-                    (cmdKey >> 8) & 0xFF,
-                    LMGetKbdType(),
-                    kUCKeyTranslateNoDeadKeysMask,
-                    &mut dead_key_state as *mut _,
-                    1,
-                    &mut unichar_count as *mut _,
-                    &mut unichar as *mut _,
-                );
+                let (logical_key_cmd, is_dead_meta) =
+                    Self::translate_logical(layout, entry.platform, (cmdKey >> 8) & 0xFF);
 
-                if unichar_count > 0 {
-                    logical_key_cmd.replace(unichar as i64);
-                }
+                // Some layouts produce distinct characters under Control, or remap
+                // shortcut matching entirely, the same way the Cmd workaround above
+                // needs its own translation rather than reusing the unmodified value.
+                let (logical_key_control, is_dead_control) =
+                    Self::translate_logical(layout, entry.platform, (controlKey >> 8) & 0xFF);
 
                 // println!(
                 //     "KEY: {:?}, {:?} {:?} {:?} {:?}",
@@ -204,6 +311,19 @@ impl PlatformKeyboardLayout {
                     logical_alt: logical_key_alt,
                     logical_alt_shift: logical_key_alt_shift,
                     logical_meta: logical_key_cmd,
+                    logical_control: logical_key_control,
+                    // Overwritten by `create_keyboard_layout` once the true
+                    // current-layout value is known; otherwise (e.g. layouts
+                    // from `get_available_layouts`) it stays equal to
+                    // `logical`, the value from the layout being translated.
+                    logical_true: logical_key,
+                    is_dead,
+                    is_dead_shift,
+                    is_dead_alt,
+                    is_dead_alt_shift,
+                    is_dead_meta,
+                    is_dead_control,
+                    is_dead_true: is_dead,
                 }
             }
         }
@@ -0,0 +1,99 @@
+#![allow(non_upper_case_globals)]
+
+use std::os::raw::{c_int, c_ulong, c_void};
+
+use core_foundation::{array::CFArrayRef, dictionary::CFDictionaryRef, string::CFStringRef};
+
+pub type CFObject = *const c_void;
+pub type CFNotificationCenterRef = *const c_void;
+pub type OSStatus = i32;
+pub type Boolean = u8;
+
+// Event modifier masks, from <Carbon/Events.h>. These are used as the
+// `modifier_key_state` argument to UCKeyTranslate, which takes UInt32, so
+// the constants are typed u32 rather than the c_int the headers declare.
+pub const cmdKey: u32 = 0x0100;
+pub const shiftKey: u32 = 0x0200;
+pub const alphaLock: u32 = 0x0400;
+pub const optionKey: u32 = 0x0800;
+pub const altKey: u32 = optionKey;
+pub const controlKey: u32 = 0x1000;
+
+// UCKeyTranslate key actions, from <Carbon/HIToolbox/Events.h>.
+pub const kUCKeyActionDown: u16 = 0;
+pub const kUCKeyActionUp: u16 = 1;
+pub const kUCKeyActionAutoKey: u16 = 2;
+pub const kUCKeyActionDisplay: u16 = 3;
+
+pub const kUCKeyTranslateNoDeadKeysMask: u32 = 1 << 0;
+
+pub const kVK_Space: u16 = 0x31;
+
+#[link(name = "Carbon", kind = "framework")]
+extern "C" {
+    pub fn UCKeyTranslate(
+        key_layout_ptr: *const c_void,
+        virtual_key_code: u16,
+        key_action: u16,
+        modifier_key_state: u32,
+        keyboard_type: u32,
+        key_translate_options: u32,
+        dead_key_state: *mut u32,
+        max_string_length: c_ulong,
+        actual_string_length: *mut c_ulong,
+        unicode_string: *mut u16,
+    ) -> OSStatus;
+
+    pub fn LMGetKbdType() -> u32;
+
+    pub static kTISPropertyUnicodeKeyLayoutData: CFStringRef;
+    pub static kTISPropertyInputSourceID: CFStringRef;
+    pub static kTISPropertyLocalizedName: CFStringRef;
+    pub static kTISPropertyInputSourceType: CFStringRef;
+    pub static kTISTypeKeyboardLayout: CFStringRef;
+
+    pub fn TISCopyCurrentASCIICapableKeyboardLayoutInputSource() -> CFObject;
+    pub fn TISCopyCurrentKeyboardInputSource() -> CFObject;
+    pub fn TISCopyCurrentKeyboardLayoutInputSource() -> CFObject;
+
+    pub fn TISCreateInputSourceList(
+        properties: CFDictionaryRef,
+        include_all_installed: Boolean,
+    ) -> CFArrayRef;
+
+    pub fn TISGetInputSourceProperty(input_source: CFObject, property_key: CFStringRef)
+        -> CFObject;
+}
+
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    pub static kTISNotifySelectedKeyboardInputSourceChanged: CFStringRef;
+
+    pub static CFNotificationSuspensionBehaviorCoalesce: c_int;
+
+    pub fn CFNotificationCenterGetDistributedCenter() -> CFNotificationCenterRef;
+
+    pub fn CFNotificationCenterAddObserver(
+        center: CFNotificationCenterRef,
+        observer: *const c_void,
+        call_back: Option<
+            extern "C" fn(
+                center: CFNotificationCenterRef,
+                observer: *mut c_void,
+                name: CFStringRef,
+                object: *const c_void,
+                user_info: CFDictionaryRef,
+            ),
+        >,
+        name: CFStringRef,
+        object: *const c_void,
+        suspension_behavior: c_int,
+    );
+
+    pub fn CFNotificationCenterRemoveObserver(
+        center: CFNotificationCenterRef,
+        observer: *const c_void,
+        name: CFStringRef,
+        object: *const c_void,
+    );
+}
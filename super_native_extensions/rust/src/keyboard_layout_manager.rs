@@ -0,0 +1,76 @@
+use std::rc::Weak;
+
+/// A single mapping between a platform (native) key code and the logical
+/// values it produces under the various modifier combinations.
+#[derive(Clone, Debug)]
+pub struct Key {
+    pub platform: i64,
+    pub physical: i64,
+    pub logical: Option<i64>,
+    pub logical_shift: Option<i64>,
+    pub logical_alt: Option<i64>,
+    pub logical_alt_shift: Option<i64>,
+    pub logical_meta: Option<i64>,
+    pub logical_control: Option<i64>,
+    /// `logical` as produced by the true current layout (e.g. Cyrillic,
+    /// Greek, Arabic) rather than its ASCII-capable substitute. Use this for
+    /// displaying the character the user actually types; keep using
+    /// `logical` (and its modifier variants) for shortcut registration,
+    /// since menu key equivalents need the ASCII-capable value to match.
+    /// For layouts listed by `get_available_layouts`, both `logical` and
+    /// `logical_true` come from that layout itself; no ASCII-capable
+    /// substitute is applied.
+    pub logical_true: Option<i64>,
+    /// Whether `logical` is a dead key (i.e. a combining diacritic that
+    /// modifies the next typed character) rather than a character typed
+    /// directly. The value reported for a dead key is its spacing glyph,
+    /// e.g. `^` for a dead circumflex.
+    pub is_dead: bool,
+    pub is_dead_shift: bool,
+    pub is_dead_alt: bool,
+    pub is_dead_alt_shift: bool,
+    pub is_dead_meta: bool,
+    pub is_dead_control: bool,
+    pub is_dead_true: bool,
+}
+
+/// Snapshot of a keyboard layout's key mappings at the time it was queried:
+/// either the active layout or one of the installed layouts.
+#[derive(Clone, Debug)]
+pub struct KeyboardLayout {
+    /// `kTISPropertyInputSourceID` of the input source this layout was read
+    /// from, e.g. `"com.apple.keylayout.US"`. Stable across relaunches, so
+    /// callers can use it to key caches or compare identity across
+    /// `keyboard_map_did_change` notifications.
+    pub id: String,
+    /// `kTISPropertyLocalizedName` of the input source, e.g. `"U.S."`.
+    pub localized_name: String,
+    pub keys: Vec<Key>,
+}
+
+/// Entry from the generated key map, pairing a platform key code with the
+/// physical key it corresponds to. `logical` is set for keys whose logical
+/// value does not depend on the active layout (e.g. function keys).
+pub struct KeyMapEntry {
+    pub platform: i64,
+    pub physical: i64,
+    pub logical: Option<i64>,
+}
+
+pub trait KeyboardLayoutDelegate {
+    fn keyboard_map_did_change(&self);
+}
+
+pub struct KeyboardLayoutManager {
+    delegate: Weak<dyn KeyboardLayoutDelegate>,
+}
+
+impl KeyboardLayoutManager {
+    pub fn new(delegate: Weak<dyn KeyboardLayoutDelegate>) -> Self {
+        Self { delegate }
+    }
+
+    pub fn delegate(&self) -> Weak<dyn KeyboardLayoutDelegate> {
+        self.delegate.clone()
+    }
+}